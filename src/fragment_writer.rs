@@ -0,0 +1,498 @@
+use byteorder::{BigEndian, WriteBytesExt};
+use std::io::{Seek, SeekFrom, Write};
+
+use crate::mp4box::sidx::{SidxBox, SidxReference};
+use crate::mp4box::{BoxHeader, BoxType, WriteBox, HEADER_SIZE};
+use crate::{Error, Result};
+
+/// One encoded access unit handed to a [`FragmentWriter`].
+///
+/// This is intentionally smaller than the crate's general sample
+/// representation - it only carries what's needed to build the `trun`
+/// table and the `sidx` reference for the fragment the sample ends up in.
+#[derive(Debug, Clone)]
+pub struct FragmentSample {
+    /// Track this sample belongs to; must match a `trex.track_id` in the
+    /// initialization segment's `mvex`.
+    pub track_id: u32,
+    pub data: Vec<u8>,
+    /// Sample duration in the track's media timescale.
+    pub duration: u32,
+    pub composition_time_offset: i32,
+    /// Sync (random-access) sample. A fragment whose first sample is a sync
+    /// sample gets `starts_with_sap`/`sap_type` 1 in its `sidx` reference;
+    /// sync samples later in a fragment are not reflected in the `sidx`.
+    /// Under [`FragmentBoundary::Gop`] every sync sample starts a new fragment.
+    pub is_sync: bool,
+}
+
+/// Decides when [`FragmentWriter::push_sample`] closes the in-progress
+/// fragment and writes it out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FragmentBoundary {
+    /// Start a new fragment at every sync sample (GOP-aligned CMAF).
+    Gop,
+    /// Start a new fragment once the current one covers at least this many
+    /// timescale units.
+    Duration(u32),
+    /// [`FragmentWriter::push_sample`] never flushes; a fragment closes only
+    /// on [`FragmentWriter::flush_fragment`] or [`FragmentWriter::finalize`].
+    Explicit,
+}
+
+#[derive(Debug, Default)]
+struct PendingFragment {
+    samples: Vec<FragmentSample>,
+    duration: u64,
+}
+
+/// Writes a sequence of samples out as CMAF/fMP4 `moof`+`mdat` fragments and
+/// builds the top-level `sidx` describing them.
+///
+/// `FragmentWriter` does not write the initialization segment - callers
+/// write their own `ftyp`+`moov` (with an `mvex` containing one `trex` per
+/// track, see [`crate::mp4box::mvex::MvexBox`]) before creating the writer.
+/// Samples are then pushed in decode order; the writer groups them into
+/// fragments per `boundary`, writes each one as soon as it's closed, and
+/// tracks its size/duration/SAP state so [`FragmentWriter::finalize`] can
+/// back-patch a matching `sidx`.
+pub struct FragmentWriter<W> {
+    writer: W,
+    boundary: FragmentBoundary,
+    sequence_number: u32,
+    sidx_reference_id: u32,
+    sidx_timescale: u32,
+    sidx_box_offset: Option<u64>,
+    expected_fragment_count: u16,
+    earliest_presentation_time: Option<u64>,
+    presentation_time: u64,
+    references: Vec<SidxReference>,
+    pending: PendingFragment,
+}
+
+impl<W: Write + Seek> FragmentWriter<W> {
+    /// Creates a writer that will emit fragments for `reference_id` (the
+    /// `track_ID` the `sidx` describes) in `timescale` units, flushing
+    /// fragments according to `boundary`.
+    pub fn new(writer: W, reference_id: u32, timescale: u32, boundary: FragmentBoundary) -> Self {
+        FragmentWriter {
+            writer,
+            boundary,
+            sequence_number: 1,
+            sidx_reference_id: reference_id,
+            sidx_timescale: timescale,
+            sidx_box_offset: None,
+            expected_fragment_count: 0,
+            earliest_presentation_time: None,
+            presentation_time: 0,
+            references: Vec::new(),
+            pending: PendingFragment::default(),
+        }
+    }
+
+    /// Reserves space for the `sidx` that [`finalize`](Self::finalize) will
+    /// back-patch once every fragment size is known. `fragment_count` must
+    /// equal the number of times [`flush_fragment`](Self::flush_fragment)
+    /// ends up running, since the reserved box is exactly sized for that
+    /// many references - `finalize` refuses to patch a box of the wrong
+    /// size. Must be called once, immediately after writing the
+    /// initialization segment and before the first
+    /// [`push_sample`](Self::push_sample)/[`flush_fragment`](Self::flush_fragment) call.
+    pub fn start(&mut self, fragment_count: u16) -> Result<()> {
+        self.sidx_box_offset = Some(self.writer.stream_position()?);
+        self.expected_fragment_count = fragment_count;
+        let placeholder = SidxBox {
+            version: 1,
+            reference_id: self.sidx_reference_id,
+            timescale: self.sidx_timescale,
+            references: vec![SidxReference::default(); fragment_count as usize],
+            ..Default::default()
+        };
+        placeholder.write_box(&mut self.writer)?;
+        Ok(())
+    }
+
+    /// Queues a sample for the current fragment, flushing the previous
+    /// fragment first if `boundary` says it's already complete.
+    ///
+    /// Every sample in a fragment ends up in the same `traf`/`trun`, so all
+    /// of them must share one `track_id` - this writer doesn't interleave
+    /// tracks within a fragment. Push samples for one track at a time (or
+    /// run a `FragmentWriter` per track) and call
+    /// [`flush_fragment`](Self::flush_fragment) between tracks.
+    pub fn push_sample(&mut self, sample: FragmentSample) -> Result<()> {
+        if let Some(pending_track_id) = self.pending.samples.first().map(|s| s.track_id) {
+            if sample.track_id != pending_track_id {
+                return Err(Error::InvalidData(
+                    "FragmentWriter::push_sample got a different track_id than the \
+                     in-progress fragment; flush_fragment() before switching tracks",
+                ));
+            }
+        }
+
+        if self.boundary == FragmentBoundary::Gop
+            && sample.is_sync
+            && !self.pending.samples.is_empty()
+        {
+            self.flush_fragment()?;
+        }
+
+        self.pending.duration += sample.duration as u64;
+        self.pending.samples.push(sample);
+
+        if let FragmentBoundary::Duration(target) = self.boundary {
+            if self.pending.duration >= target as u64 {
+                self.flush_fragment()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Closes the in-progress fragment, if it has any samples, and writes
+    /// its `moof`+`mdat` to the output, recording a [`SidxReference`] for
+    /// it.
+    pub fn flush_fragment(&mut self) -> Result<()> {
+        if self.pending.samples.is_empty() {
+            return Ok(());
+        }
+        if self.sidx_box_offset.is_none() {
+            return Err(Error::InvalidData("FragmentWriter::start was not called"));
+        }
+
+        let fragment = std::mem::take(&mut self.pending);
+        let track_id = fragment.samples[0].track_id;
+        let starts_with_sap = fragment.samples[0].is_sync;
+
+        let moof_size = self.write_moof(self.sequence_number, track_id, &fragment.samples)?;
+        let mdat_size = self.write_mdat(&fragment.samples)?;
+
+        if self.earliest_presentation_time.is_none() {
+            // `sidx` EPT is on the presentation timeline, so the first
+            // fragment's leading sample composition offset must be folded
+            // in - not just its decode time - or streams with B-frames get
+            // a wrong (too-early) earliest_presentation_time.
+            let first_cto = fragment.samples[0].composition_time_offset as i64;
+            let ept = self.presentation_time as i64 + first_cto;
+            if ept < 0 {
+                return Err(Error::InvalidData(
+                    "first fragment's composition_time_offset made earliest_presentation_time negative",
+                ));
+            }
+            self.earliest_presentation_time = Some(ept as u64);
+        }
+
+        self.references.push(SidxReference {
+            reference_type: 0,
+            referenced_size: (moof_size + mdat_size) as u32,
+            subsegment_duration: fragment.duration as u32,
+            starts_with_sap: starts_with_sap as u8,
+            sap_type: if starts_with_sap { 1 } else { 0 },
+            sap_delta_time: 0,
+        });
+
+        self.presentation_time += fragment.duration;
+        self.sequence_number += 1;
+
+        Ok(())
+    }
+
+    fn write_moof(
+        &mut self,
+        sequence_number: u32,
+        track_id: u32,
+        samples: &[FragmentSample],
+    ) -> Result<u64> {
+        let mfhd_size = HEADER_SIZE + 8; // version/flags + sequence_number
+        let tfhd_size = HEADER_SIZE + 4 + 4; // version/flags + track_id
+        let tfdt_size = HEADER_SIZE + 4 + 8; // version(1)/flags + baseMediaDecodeTime
+        let trun_size = HEADER_SIZE + 4 + 4 + 4 + 16 * samples.len() as u64;
+        let traf_size = HEADER_SIZE + tfhd_size + tfdt_size + trun_size;
+        let moof_size = HEADER_SIZE + mfhd_size + traf_size;
+
+        BoxHeader::new(BoxType::MoofBox, moof_size).write(&mut self.writer)?;
+
+        BoxHeader::new(BoxType::MfhdBox, mfhd_size).write(&mut self.writer)?;
+        self.writer.write_u32::<BigEndian>(0)?; // version + flags
+        self.writer.write_u32::<BigEndian>(sequence_number)?;
+
+        BoxHeader::new(BoxType::TrafBox, traf_size).write(&mut self.writer)?;
+
+        BoxHeader::new(BoxType::TfhdBox, tfhd_size).write(&mut self.writer)?;
+        self.writer.write_u32::<BigEndian>(0x02_0000)?; // default-base-is-moof
+        self.writer.write_u32::<BigEndian>(track_id)?;
+
+        BoxHeader::new(BoxType::TfdtBox, tfdt_size).write(&mut self.writer)?;
+        self.writer.write_u32::<BigEndian>(1 << 24)?; // version = 1
+        self.writer.write_u64::<BigEndian>(self.presentation_time)?;
+
+        // data-offset + sample-duration + sample-size + sample-flags +
+        // sample-composition-time-offsets present. Always write a version-1
+        // `trun`: version 0 stores the composition offset as *unsigned*, so
+        // a negative offset (B-frames presented before their decode time)
+        // would round-trip as a huge positive value under a conformant
+        // demuxer.
+        let trun_version_and_flags: u32 =
+            (1 << 24) | 0x00_0001 | 0x00_0100 | 0x00_0200 | 0x00_0400 | 0x00_0800;
+        BoxHeader::new(BoxType::TrunBox, trun_size).write(&mut self.writer)?;
+        self.writer.write_u32::<BigEndian>(trun_version_and_flags)?;
+        self.writer.write_u32::<BigEndian>(samples.len() as u32)?;
+        self.writer
+            .write_i32::<BigEndian>((moof_size + HEADER_SIZE) as i32)?; // data_offset: moof start -> mdat payload
+        for sample in samples {
+            self.writer.write_u32::<BigEndian>(sample.duration)?;
+            self.writer.write_u32::<BigEndian>(sample.data.len() as u32)?;
+            let flags = if sample.is_sync {
+                0x0200_0000
+            } else {
+                0x0101_0000 // sample_is_difference_sample + no-key-frame flags
+            };
+            self.writer.write_u32::<BigEndian>(flags)?;
+            self.writer
+                .write_i32::<BigEndian>(sample.composition_time_offset)?;
+        }
+
+        Ok(moof_size)
+    }
+
+    fn write_mdat(&mut self, samples: &[FragmentSample]) -> Result<u64> {
+        let payload_size: u64 = samples.iter().map(|s| s.data.len() as u64).sum();
+        let mdat_size = HEADER_SIZE + payload_size;
+        BoxHeader::new(BoxType::MdatBox, mdat_size).write(&mut self.writer)?;
+        for sample in samples {
+            self.writer.write_all(&sample.data)?;
+        }
+        Ok(mdat_size)
+    }
+
+    /// Flushes any pending fragment, then back-patches the `sidx` reserved
+    /// by [`start`](Self::start) with the now-known reference list and
+    /// `earliest_presentation_time`. `first_offset` is left at `0` since the
+    /// fragments immediately follow the `sidx` with nothing in between.
+    pub fn finalize(mut self) -> Result<W> {
+        self.flush_fragment()?;
+
+        let sidx_offset = self
+            .sidx_box_offset
+            .ok_or(Error::InvalidData("FragmentWriter::start was not called"))?;
+        if self.references.len() != self.expected_fragment_count as usize {
+            return Err(Error::InvalidData(
+                "number of flushed fragments does not match the count reserved in FragmentWriter::start",
+            ));
+        }
+
+        let sidx = SidxBox {
+            version: 1,
+            reference_id: self.sidx_reference_id,
+            timescale: self.sidx_timescale,
+            earliest_presentation_time: self.earliest_presentation_time.unwrap_or(0),
+            first_offset: 0,
+            references: self.references,
+            children: Vec::new(),
+        };
+
+        let end = self.writer.stream_position()?;
+        self.writer.seek(SeekFrom::Start(sidx_offset))?;
+        sidx.write_box(&mut self.writer)?;
+        self.writer.seek(SeekFrom::Start(end))?;
+
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::ReadBytesExt;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    fn sample(track_id: u32, duration: u32, is_sync: bool, len: usize) -> FragmentSample {
+        FragmentSample {
+            track_id,
+            data: vec![0u8; len],
+            duration,
+            composition_time_offset: 0,
+            is_sync,
+        }
+    }
+
+    #[test]
+    fn test_gop_boundary_flushes_on_sync_sample() {
+        let mut w = FragmentWriter::new(Cursor::new(Vec::new()), 1, 1000, FragmentBoundary::Gop);
+        w.start(2).unwrap();
+        w.push_sample(sample(1, 100, true, 10)).unwrap();
+        w.push_sample(sample(1, 100, false, 10)).unwrap();
+        w.push_sample(sample(1, 100, true, 10)).unwrap(); // starts the 2nd fragment
+        w.push_sample(sample(1, 100, false, 10)).unwrap();
+        let buf = w.finalize().unwrap().into_inner();
+
+        let mut reader = Cursor::new(buf);
+        let sidx = SidxBox::read_chain(&mut reader).unwrap();
+        assert_eq!(sidx.references.len(), 2);
+        assert_eq!(sidx.references[0].subsegment_duration, 200);
+        assert_eq!(sidx.references[1].subsegment_duration, 200);
+    }
+
+    #[test]
+    fn test_duration_boundary_flushes_at_target() {
+        let mut w = FragmentWriter::new(
+            Cursor::new(Vec::new()),
+            1,
+            1000,
+            FragmentBoundary::Duration(200),
+        );
+        w.start(2).unwrap();
+        for _ in 0..4 {
+            w.push_sample(sample(1, 100, false, 5)).unwrap();
+        }
+        let buf = w.finalize().unwrap().into_inner();
+
+        let mut reader = Cursor::new(buf);
+        let sidx = SidxBox::read_chain(&mut reader).unwrap();
+        assert_eq!(sidx.references.len(), 2);
+        assert_eq!(sidx.references[0].subsegment_duration, 200);
+        assert_eq!(sidx.references[1].subsegment_duration, 200);
+    }
+
+    #[test]
+    fn test_explicit_boundary_requires_manual_flush() {
+        let mut w = FragmentWriter::new(
+            Cursor::new(Vec::new()),
+            1,
+            1000,
+            FragmentBoundary::Explicit,
+        );
+        w.start(1).unwrap();
+        for _ in 0..4 {
+            w.push_sample(sample(1, 100, false, 5)).unwrap();
+        }
+        w.flush_fragment().unwrap();
+        let buf = w.finalize().unwrap().into_inner();
+
+        let mut reader = Cursor::new(buf);
+        let sidx = SidxBox::read_chain(&mut reader).unwrap();
+        assert_eq!(sidx.references.len(), 1);
+        assert_eq!(sidx.references[0].subsegment_duration, 400);
+    }
+
+    #[test]
+    fn test_finalize_sidx_matches_written_fragment_sizes() {
+        let mut w = FragmentWriter::new(
+            Cursor::new(Vec::new()),
+            7,
+            1000,
+            FragmentBoundary::Explicit,
+        );
+        w.start(2).unwrap();
+        w.push_sample(sample(7, 100, true, 20)).unwrap();
+        w.push_sample(sample(7, 100, false, 30)).unwrap();
+        w.flush_fragment().unwrap();
+        w.push_sample(sample(7, 100, true, 15)).unwrap();
+        w.flush_fragment().unwrap();
+        let buf = w.finalize().unwrap().into_inner();
+
+        let mut reader = Cursor::new(buf);
+        let sidx = SidxBox::read_chain(&mut reader).unwrap();
+        assert_eq!(sidx.references.len(), 2);
+
+        let mut offset = reader.position();
+        for reference in &sidx.references {
+            let moof_header = BoxHeader::read(&mut reader).unwrap();
+            assert_eq!(moof_header.name, BoxType::MoofBox);
+            reader.set_position(offset + moof_header.size);
+
+            let mdat_header = BoxHeader::read(&mut reader).unwrap();
+            assert_eq!(mdat_header.name, BoxType::MdatBox);
+
+            let fragment_size = moof_header.size + mdat_header.size;
+            assert_eq!(reference.referenced_size as u64, fragment_size);
+
+            offset += fragment_size;
+            reader.set_position(offset);
+        }
+
+        assert_eq!(sidx.references[0].subsegment_duration, 200);
+        assert_eq!(sidx.references[1].subsegment_duration, 100);
+    }
+
+    #[test]
+    fn test_earliest_presentation_time_folds_in_first_sample_cto() {
+        let mut w = FragmentWriter::new(
+            Cursor::new(Vec::new()),
+            1,
+            1000,
+            FragmentBoundary::Explicit,
+        );
+        w.start(1).unwrap();
+        let mut first = sample(1, 100, true, 10);
+        first.composition_time_offset = 40;
+        w.push_sample(first).unwrap();
+        w.push_sample(sample(1, 100, false, 10)).unwrap();
+        let buf = w.finalize().unwrap().into_inner();
+
+        let mut reader = Cursor::new(buf);
+        let sidx = SidxBox::read_chain(&mut reader).unwrap();
+        assert_eq!(sidx.earliest_presentation_time, 40);
+    }
+
+    #[test]
+    fn test_trun_round_trips_negative_composition_time_offset() {
+        let mut w = FragmentWriter::new(
+            Cursor::new(Vec::new()),
+            1,
+            1000,
+            FragmentBoundary::Explicit,
+        );
+        w.start(1).unwrap();
+        let mut first = sample(1, 100, true, 10);
+        first.composition_time_offset = -25;
+        w.push_sample(first).unwrap();
+        w.flush_fragment().unwrap();
+        let buf = w.finalize().unwrap().into_inner();
+
+        let mut reader = Cursor::new(buf);
+        SidxBox::read_chain(&mut reader).unwrap();
+
+        let moof_header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(moof_header.name, BoxType::MoofBox);
+        let mfhd_header = BoxHeader::read(&mut reader).unwrap();
+        reader.set_position(reader.position() + mfhd_header.size - HEADER_SIZE);
+        let traf_header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(traf_header.name, BoxType::TrafBox);
+        let tfhd_header = BoxHeader::read(&mut reader).unwrap();
+        reader.set_position(reader.position() + tfhd_header.size - HEADER_SIZE);
+        let tfdt_header = BoxHeader::read(&mut reader).unwrap();
+        reader.set_position(reader.position() + tfdt_header.size - HEADER_SIZE);
+
+        let trun_header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(trun_header.name, BoxType::TrunBox);
+        let version_and_flags = reader.read_u32::<BigEndian>().unwrap();
+        assert_eq!(
+            version_and_flags >> 24,
+            1,
+            "trun must be version 1 to carry a signed composition_time_offset"
+        );
+        let sample_count = reader.read_u32::<BigEndian>().unwrap();
+        assert_eq!(sample_count, 1);
+        let _data_offset = reader.read_i32::<BigEndian>().unwrap();
+        let _duration = reader.read_u32::<BigEndian>().unwrap();
+        let _size = reader.read_u32::<BigEndian>().unwrap();
+        let _flags = reader.read_u32::<BigEndian>().unwrap();
+        let cto = reader.read_i32::<BigEndian>().unwrap();
+        assert_eq!(cto, -25);
+    }
+
+    #[test]
+    fn test_push_sample_rejects_interleaved_track_id() {
+        let mut w = FragmentWriter::new(
+            Cursor::new(Vec::new()),
+            1,
+            1000,
+            FragmentBoundary::Explicit,
+        );
+        w.start(1).unwrap();
+        w.push_sample(sample(1, 100, true, 10)).unwrap();
+        assert!(w.push_sample(sample(2, 100, false, 10)).is_err());
+    }
+}
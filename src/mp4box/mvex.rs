@@ -101,3 +101,170 @@ impl<W: Write> WriteBox<&mut W> for MvexBox {
         Ok(size)
     }
 }
+
+/// Per-track defaults used by [`MvexBox::build`] to populate a `trex`
+/// entry.
+#[derive(Debug, Clone, Copy)]
+pub struct TrexDefaults {
+    pub track_id: u32,
+    pub default_sample_description_index: u32,
+    pub default_sample_duration: u32,
+    pub default_sample_size: u32,
+    pub default_sample_flags: u32,
+}
+
+/// Errors produced by [`MvexBox::build`]/[`MvexBox::validate`] when the
+/// fragment defaults don't line up with the tracks they're meant to
+/// describe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MvexValidationError {
+    /// No tracks were given to [`MvexBox::build`].
+    NoTracks,
+    /// A `trex.track_id` has no matching track in the parent `moov`.
+    UnknownTrackId(u32),
+    /// `mehd.fragment_duration` doesn't match the sum of the per-fragment
+    /// durations (both in the movie timescale).
+    FragmentDurationMismatch { expected: u64, actual: u64 },
+}
+
+impl std::fmt::Display for MvexValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoTracks => write!(f, "no tracks given to build an mvex for"),
+            Self::UnknownTrackId(track_id) => {
+                write!(f, "trex.track_id {track_id} has no matching track")
+            }
+            Self::FragmentDurationMismatch { expected, actual } => write!(
+                f,
+                "mehd.fragment_duration {actual} does not match the expected {expected}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MvexValidationError {}
+
+impl MvexBox {
+    /// Builds an `mvex` with one `trex` per entry in `tracks`, and, if
+    /// `fragment_durations` is given, an `mehd` whose `fragment_duration` is
+    /// the sum of those durations (movie timescale units).
+    pub fn build(
+        tracks: &[TrexDefaults],
+        fragment_durations: Option<&[u64]>,
+    ) -> std::result::Result<MvexBox, MvexValidationError> {
+        if tracks.is_empty() {
+            return Err(MvexValidationError::NoTracks);
+        }
+
+        let trex = tracks
+            .iter()
+            .map(|t| TrexBox {
+                version: 0,
+                flags: 0,
+                track_id: t.track_id,
+                default_sample_description_index: t.default_sample_description_index,
+                default_sample_duration: t.default_sample_duration,
+                default_sample_size: t.default_sample_size,
+                default_sample_flags: t.default_sample_flags,
+            })
+            .collect();
+
+        let mehd = fragment_durations.map(|durations| MehdBox {
+            version: 0,
+            flags: 0,
+            fragment_duration: durations.iter().sum(),
+        });
+
+        Ok(MvexBox { mehd, trex })
+    }
+
+    /// Checks that every `trex.track_id` is one of `track_ids` (the tracks
+    /// declared in the parent `moov`) and, if an `mehd` is present, that its
+    /// `fragment_duration` equals the sum of `fragment_durations` (both in
+    /// the movie timescale).
+    pub fn validate(
+        &self,
+        track_ids: &[u32],
+        fragment_durations: &[u64],
+    ) -> std::result::Result<(), MvexValidationError> {
+        for trex in &self.trex {
+            if !track_ids.contains(&trex.track_id) {
+                return Err(MvexValidationError::UnknownTrackId(trex.track_id));
+            }
+        }
+
+        if let Some(mehd) = &self.mehd {
+            let expected: u64 = fragment_durations.iter().sum();
+            if mehd.fragment_duration != expected {
+                return Err(MvexValidationError::FragmentDurationMismatch {
+                    expected,
+                    actual: mehd.fragment_duration,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults(track_id: u32) -> TrexDefaults {
+        TrexDefaults {
+            track_id,
+            default_sample_description_index: 1,
+            default_sample_duration: 1000,
+            default_sample_size: 0,
+            default_sample_flags: 0x0101_0000,
+        }
+    }
+
+    #[test]
+    fn test_build_and_validate_success() {
+        let mvex = MvexBox::build(&[defaults(1), defaults(2)], Some(&[1000, 2000])).unwrap();
+
+        assert_eq!(mvex.trex.len(), 2);
+        assert_eq!(mvex.trex[0].track_id, 1);
+        assert_eq!(mvex.trex[1].track_id, 2);
+        assert_eq!(mvex.mehd.as_ref().unwrap().fragment_duration, 3000);
+
+        assert_eq!(mvex.validate(&[1, 2], &[1000, 2000]), Ok(()));
+    }
+
+    #[test]
+    fn test_build_without_fragment_durations_has_no_mehd() {
+        let mvex = MvexBox::build(&[defaults(1)], None).unwrap();
+        assert!(mvex.mehd.is_none());
+    }
+
+    #[test]
+    fn test_build_rejects_no_tracks() {
+        assert_eq!(
+            MvexBox::build(&[], None).unwrap_err(),
+            MvexValidationError::NoTracks
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_unknown_track_id() {
+        let mvex = MvexBox::build(&[defaults(1), defaults(2)], None).unwrap();
+        assert_eq!(
+            mvex.validate(&[1], &[]).unwrap_err(),
+            MvexValidationError::UnknownTrackId(2)
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_fragment_duration_mismatch() {
+        let mvex = MvexBox::build(&[defaults(1)], Some(&[1000, 2000])).unwrap();
+        assert_eq!(
+            mvex.validate(&[1], &[1000]).unwrap_err(),
+            MvexValidationError::FragmentDurationMismatch {
+                expected: 1000,
+                actual: 3000,
+            }
+        );
+    }
+}
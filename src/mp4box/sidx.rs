@@ -1,5 +1,6 @@
 use byteorder::{BigEndian, ReadBytesExt};
-use serde::Serialize;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
 use std::io::{self, Read, Seek, SeekFrom};
 
 use crate::mp4box::*;
@@ -13,9 +14,19 @@ pub struct SidxBox {
     pub earliest_presentation_time: u64,
     pub first_offset: u64,
     pub references: Vec<SidxReference>,
+    /// Nested `sidx` boxes for `references` entries with
+    /// `reference_type == 1`, in the same order as those entries. Not part
+    /// of this box's own binary layout (hierarchical DASH On-Demand indexes
+    /// store each nested `sidx` as a sibling box following its parent), so
+    /// plain `read_box`/`write_box` never touch this field. Use
+    /// [`SidxBox::read_chain`]/[`SidxBox::write_chain`] to read or write a
+    /// whole tree at once, or [`SidxBox::attach_children`] to assemble one
+    /// by hand from boxes read elsewhere.
+    #[serde(skip)]
+    pub children: Vec<SidxBox>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct SidxReference {
     pub reference_type: u8,
     pub referenced_size: u32,
@@ -25,6 +36,83 @@ pub struct SidxReference {
     pub sap_delta_time: u32,
 }
 
+/// Decoded meaning of [`SidxReference::reference_type`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SidxReferenceKind {
+    /// `reference_type == 0`: the reference points directly at media (e.g.
+    /// a `moof`+`mdat` pair).
+    Media,
+    /// `reference_type == 1`: the reference points at another `sidx` box
+    /// (hierarchical index), see [`SidxBox::attach_children`].
+    Index,
+}
+
+/// ISO/IEC 14496-12 Stream Access Point type carried in
+/// [`SidxReference::sap_type`]. `0` means "unspecified/not signaled"; the
+/// spec only assigns meaning to `1..=6`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SidxSapType {
+    Unspecified,
+    Type1,
+    Type2,
+    Type3,
+    Type4,
+    Type5,
+    Type6,
+}
+
+/// Serializes the raw packed fields alongside [`SidxReference::reference_kind`]
+/// and [`SidxReference::sap`] so downstream JSON tooling gets the decoded
+/// meaning without reinterpreting `reference_type`/`sap_type` by hand.
+impl Serialize for SidxReference {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("SidxReference", 8)?;
+        state.serialize_field("reference_type", &self.reference_type)?;
+        state.serialize_field("reference_kind", &self.reference_kind())?;
+        state.serialize_field("referenced_size", &self.referenced_size)?;
+        state.serialize_field("subsegment_duration", &self.subsegment_duration)?;
+        state.serialize_field("starts_with_sap", &self.starts_with_sap)?;
+        state.serialize_field("sap_type", &self.sap_type)?;
+        state.serialize_field("sap", &self.sap())?;
+        state.serialize_field("sap_delta_time", &self.sap_delta_time)?;
+        state.end()
+    }
+}
+
+impl SidxReference {
+    /// Decodes the raw `reference_type` bit into [`SidxReferenceKind`].
+    pub fn reference_kind(&self) -> SidxReferenceKind {
+        if self.reference_type == 1 {
+            SidxReferenceKind::Index
+        } else {
+            SidxReferenceKind::Media
+        }
+    }
+
+    /// Decodes the raw `sap_type` nibble into [`SidxSapType`].
+    pub fn sap(&self) -> SidxSapType {
+        match self.sap_type {
+            1 => SidxSapType::Type1,
+            2 => SidxSapType::Type2,
+            3 => SidxSapType::Type3,
+            4 => SidxSapType::Type4,
+            5 => SidxSapType::Type5,
+            6 => SidxSapType::Type6,
+            _ => SidxSapType::Unspecified,
+        }
+    }
+}
+
+/// A contiguous byte range a client should fetch, relative to the first
+/// byte of the top-level `sidx` that [`SidxBox::resolve_byte_range`] was
+/// called on - i.e. with the whole `sidx` chain's size and every
+/// `first_offset` along the path already added in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SidxByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
 impl SidxBox {
     pub fn get_type(&self) -> BoxType {
         BoxType::SidxBox
@@ -34,6 +122,158 @@ impl SidxBox {
             + (4 + (if self.version == 1 { 2 } else { 1 }) * 2) * 4
             + (12 * self.references.len() as u64)
     }
+
+    /// Attaches nested `sidx` boxes to this box's `reference_type == 1`
+    /// ("index") entries, in document order, turning a flat reference list
+    /// into the tree a hierarchical DASH On-Demand index describes.
+    /// `children` must contain exactly one box per such entry.
+    pub fn attach_children(&mut self, children: Vec<SidxBox>) -> Result<()> {
+        let expected = self
+            .references
+            .iter()
+            .filter(|r| r.reference_type == 1)
+            .count();
+        if children.len() != expected {
+            return Err(Error::InvalidData(
+                "number of child sidx boxes does not match the reference_type==1 entries",
+            ));
+        }
+        self.children = children;
+        Ok(())
+    }
+
+    /// Reads a `sidx` and, if any of its references are `reference_type ==
+    /// 1` ("index"), recursively reads and attaches the nested `sidx`
+    /// boxes that immediately follow it in the stream (depth-first, in
+    /// reference order) - the layout produced by tools that daisy-chain
+    /// hierarchical DASH On-Demand indexes. A plain (non-nested) `sidx`
+    /// round-trips through this exactly like [`SidxBox::read_box`].
+    pub fn read_chain<R: Read + Seek>(reader: &mut R) -> Result<SidxBox> {
+        let header = BoxHeader::read(reader)?;
+        if header.name != BoxType::SidxBox {
+            return Err(Error::InvalidData("expected a sidx box"));
+        }
+        let mut sidx = SidxBox::read_box(reader, header.size)?;
+
+        let nested_count = sidx
+            .references
+            .iter()
+            .filter(|r| r.reference_type == 1)
+            .count();
+        let mut children = Vec::with_capacity(nested_count);
+        for _ in 0..nested_count {
+            children.push(SidxBox::read_chain(reader)?);
+        }
+        sidx.children = children;
+
+        Ok(sidx)
+    }
+
+    /// Writes this `sidx` followed by every attached child, recursively via
+    /// `write_chain`, mirroring the layout [`SidxBox::read_chain`] expects.
+    /// A box with no `children` behaves exactly like [`SidxBox::write_box`].
+    pub fn write_chain<W: Write>(&self, writer: &mut W) -> Result<u64> {
+        let mut size = self.write_box(writer)?;
+        for child in &self.children {
+            size += child.write_chain(writer)?;
+        }
+        Ok(size)
+    }
+
+    /// Total bytes this `sidx` and its whole attached subtree occupy when
+    /// written with [`SidxBox::write_chain`] - its own box plus every
+    /// descendant's, depth-first. `write_chain` never writes media, only
+    /// the chain of `sidx` boxes itself, so this is where that chain ends
+    /// and the media it indexes must begin.
+    fn subtree_size(&self) -> u64 {
+        self.get_size()
+            + self
+                .children
+                .iter()
+                .map(SidxBox::subtree_size)
+                .sum::<u64>()
+    }
+
+    /// Total media bytes this `sidx` indexes - the sum of its `reference_type
+    /// == 0` entries' `referenced_size`, descending into nested boxes
+    /// (attached via [`SidxBox::attach_children`]) for `reference_type == 1`
+    /// entries instead of counting their own (non-media) `referenced_size`.
+    fn total_media_size(&self) -> u64 {
+        let mut child_index = 0usize;
+        self.references
+            .iter()
+            .map(|reference| {
+                if reference.reference_type == 1 {
+                    let size = self
+                        .children
+                        .get(child_index)
+                        .map(SidxBox::total_media_size)
+                        .unwrap_or(reference.referenced_size as u64);
+                    child_index += 1;
+                    size
+                } else {
+                    reference.referenced_size as u64
+                }
+            })
+            .sum()
+    }
+
+    /// Walks this `sidx`, descending into nested boxes attached via
+    /// [`SidxBox::attach_children`], to find the byte range a client must
+    /// fetch to retrieve the subsegment containing `presentation_time`.
+    /// Returns `None` if `presentation_time` falls outside every reference.
+    ///
+    /// Matches the layout [`SidxBox::write_chain`]/[`SidxBox::read_chain`]
+    /// produce: the whole nested `sidx` chain is one contiguous block with
+    /// no media in between, so every byte range this resolves starts after
+    /// [`SidxBox::subtree_size`] and advances through the indexed media in
+    /// presentation order from there.
+    pub fn resolve_byte_range(&self, presentation_time: u64) -> Option<SidxByteRange> {
+        self.resolve_byte_range_from(presentation_time, self.subtree_size())
+    }
+
+    fn resolve_byte_range_from(
+        &self,
+        presentation_time: u64,
+        media_base: u64,
+    ) -> Option<SidxByteRange> {
+        let mut offset = media_base + self.first_offset;
+        let mut time = self.earliest_presentation_time;
+        let mut child_index = 0usize;
+
+        for reference in &self.references {
+            let duration = reference.subsegment_duration as u64;
+
+            if reference.reference_type == 1 {
+                let child = self.children.get(child_index);
+                child_index += 1;
+
+                if presentation_time >= time && presentation_time < time + duration {
+                    return child.and_then(|child| {
+                        child.resolve_byte_range_from(presentation_time, offset)
+                    });
+                }
+
+                offset += child
+                    .map(SidxBox::total_media_size)
+                    .unwrap_or(reference.referenced_size as u64);
+                time += duration;
+                continue;
+            }
+
+            let size = reference.referenced_size as u64;
+            if presentation_time >= time && presentation_time < time + duration {
+                return Some(SidxByteRange {
+                    start: offset,
+                    end: offset + size,
+                });
+            }
+            offset += size;
+            time += duration;
+        }
+
+        None
+    }
 }
 
 impl Mp4Box for SidxBox {
@@ -50,7 +290,22 @@ impl Mp4Box for SidxBox {
     }
 
     fn summary(&self) -> Result<String> {
-        todo!()
+        let total_duration: u64 = self
+            .references
+            .iter()
+            .map(|r| r.subsegment_duration as u64)
+            .sum();
+        let all_sap = !self.references.is_empty()
+            && self.references.iter().all(|r| r.starts_with_sap == 1);
+        let s = format!(
+            "timescale={} earliest_presentation_time={} reference_count={} total_duration={} all_sap={}",
+            self.timescale,
+            self.earliest_presentation_time,
+            self.references.len(),
+            total_duration,
+            all_sap
+        );
+        Ok(s)
     }
 }
 
@@ -113,6 +368,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for SidxBox {
             earliest_presentation_time,
             first_offset,
             references,
+            children: Vec::new(),
         })
     }
 }
@@ -198,4 +454,355 @@ mod tests {
 
         assert_eq!(writer.into_inner(), data);
     }
+
+    #[test]
+    fn test_resolve_byte_range_through_nested_sidx() {
+        let mut top = SidxBox {
+            version: 0,
+            reference_id: 1,
+            timescale: 1000,
+            earliest_presentation_time: 0,
+            first_offset: 0,
+            references: vec![SidxReference {
+                reference_type: 1,
+                referenced_size: 1000,
+                subsegment_duration: 2000,
+                starts_with_sap: 1,
+                sap_type: 1,
+                sap_delta_time: 0,
+            }],
+            ..Default::default()
+        };
+
+        let leaf = SidxBox {
+            version: 0,
+            reference_id: 1,
+            timescale: 1000,
+            earliest_presentation_time: 0,
+            first_offset: 0,
+            references: vec![
+                SidxReference {
+                    reference_type: 0,
+                    referenced_size: 400,
+                    subsegment_duration: 1000,
+                    starts_with_sap: 1,
+                    sap_type: 1,
+                    sap_delta_time: 0,
+                },
+                SidxReference {
+                    reference_type: 0,
+                    referenced_size: 600,
+                    subsegment_duration: 1000,
+                    starts_with_sap: 1,
+                    sap_type: 1,
+                    sap_delta_time: 0,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let top_size = top.get_size();
+        let leaf_size = leaf.get_size();
+        top.attach_children(vec![leaf]).unwrap();
+
+        // write_chain writes the whole sidx chain ([top][leaf]) contiguously
+        // with no media in between, so media only starts after both boxes.
+        assert_eq!(
+            top.resolve_byte_range(1200),
+            Some(SidxByteRange {
+                start: top_size + leaf_size + 400,
+                end: top_size + leaf_size + 1000,
+            })
+        );
+        assert_eq!(top.resolve_byte_range(5000), None);
+    }
+
+    #[test]
+    fn test_resolve_byte_range_applies_first_offset() {
+        let mut top = SidxBox {
+            version: 0,
+            reference_id: 1,
+            timescale: 1000,
+            earliest_presentation_time: 0,
+            first_offset: 300,
+            references: vec![SidxReference {
+                reference_type: 1,
+                referenced_size: 1000,
+                subsegment_duration: 2000,
+                starts_with_sap: 1,
+                sap_type: 1,
+                sap_delta_time: 0,
+            }],
+            ..Default::default()
+        };
+
+        let leaf = SidxBox {
+            version: 0,
+            reference_id: 1,
+            timescale: 1000,
+            earliest_presentation_time: 0,
+            first_offset: 50,
+            references: vec![
+                SidxReference {
+                    reference_type: 0,
+                    referenced_size: 400,
+                    subsegment_duration: 1000,
+                    starts_with_sap: 1,
+                    sap_type: 1,
+                    sap_delta_time: 0,
+                },
+                SidxReference {
+                    reference_type: 0,
+                    referenced_size: 600,
+                    subsegment_duration: 1000,
+                    starts_with_sap: 1,
+                    sap_type: 1,
+                    sap_delta_time: 0,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let top_size = top.get_size();
+        let leaf_size = leaf.get_size();
+        top.attach_children(vec![leaf]).unwrap();
+
+        // top.get_size() + leaf.get_size() (the whole sidx chain, written
+        // contiguously by write_chain) + top.first_offset (300) + leaf's
+        // first_offset (50) + the 400-byte first leaf reference that
+        // precedes the matching one.
+        assert_eq!(
+            top.resolve_byte_range(1200),
+            Some(SidxByteRange {
+                start: top_size + leaf_size + 300 + 50 + 400,
+                end: top_size + leaf_size + 300 + 50 + 1000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_write_chain_round_trip() {
+        let leaf_a = SidxBox {
+            version: 0,
+            reference_id: 1,
+            timescale: 1000,
+            earliest_presentation_time: 0,
+            first_offset: 0,
+            references: vec![SidxReference {
+                reference_type: 0,
+                referenced_size: 400,
+                subsegment_duration: 1000,
+                starts_with_sap: 1,
+                sap_type: 1,
+                sap_delta_time: 0,
+            }],
+            ..Default::default()
+        };
+        let leaf_b = SidxBox {
+            version: 0,
+            reference_id: 1,
+            timescale: 1000,
+            earliest_presentation_time: 1000,
+            first_offset: 0,
+            references: vec![SidxReference {
+                reference_type: 0,
+                referenced_size: 600,
+                subsegment_duration: 1000,
+                starts_with_sap: 1,
+                sap_type: 1,
+                sap_delta_time: 0,
+            }],
+            ..Default::default()
+        };
+
+        let mut top = SidxBox {
+            version: 0,
+            reference_id: 1,
+            timescale: 1000,
+            earliest_presentation_time: 0,
+            first_offset: 0,
+            references: vec![
+                SidxReference {
+                    reference_type: 1,
+                    referenced_size: 1000,
+                    subsegment_duration: 1000,
+                    starts_with_sap: 1,
+                    sap_type: 1,
+                    sap_delta_time: 0,
+                },
+                SidxReference {
+                    reference_type: 1,
+                    referenced_size: 1000,
+                    subsegment_duration: 1000,
+                    starts_with_sap: 1,
+                    sap_type: 1,
+                    sap_delta_time: 0,
+                },
+            ],
+            ..Default::default()
+        };
+        top.attach_children(vec![leaf_a, leaf_b]).unwrap();
+
+        let mut buf = Vec::new();
+        top.write_chain(&mut buf).unwrap();
+
+        let mut reader = Cursor::new(buf);
+        let read_back = SidxBox::read_chain(&mut reader).unwrap();
+
+        assert_eq!(read_back.references.len(), 2);
+        assert_eq!(read_back.children.len(), 2);
+        assert_eq!(read_back.children[0].references[0].referenced_size, 400);
+        assert_eq!(read_back.children[1].references[0].referenced_size, 600);
+    }
+
+    #[test]
+    fn test_resolve_byte_range_on_chain_round_tripped_tree_with_two_leaves() {
+        let leaf_a = SidxBox {
+            version: 0,
+            reference_id: 1,
+            timescale: 1000,
+            earliest_presentation_time: 0,
+            first_offset: 0,
+            references: vec![SidxReference {
+                reference_type: 0,
+                referenced_size: 400,
+                subsegment_duration: 1000,
+                starts_with_sap: 1,
+                sap_type: 1,
+                sap_delta_time: 0,
+            }],
+            ..Default::default()
+        };
+        let leaf_b = SidxBox {
+            version: 0,
+            reference_id: 1,
+            timescale: 1000,
+            earliest_presentation_time: 1000,
+            first_offset: 0,
+            references: vec![SidxReference {
+                reference_type: 0,
+                referenced_size: 600,
+                subsegment_duration: 1000,
+                starts_with_sap: 1,
+                sap_type: 1,
+                sap_delta_time: 0,
+            }],
+            ..Default::default()
+        };
+        let (leaf_a_size, leaf_b_size) = (leaf_a.get_size(), leaf_b.get_size());
+
+        let mut top = SidxBox {
+            version: 0,
+            reference_id: 1,
+            timescale: 1000,
+            earliest_presentation_time: 0,
+            first_offset: 0,
+            references: vec![
+                SidxReference {
+                    reference_type: 1,
+                    // Deliberately wrong/stale - resolve_byte_range must not
+                    // need this to be accurate for a tree it can measure
+                    // itself via write_chain's actual layout.
+                    referenced_size: 1,
+                    subsegment_duration: 1000,
+                    starts_with_sap: 1,
+                    sap_type: 1,
+                    sap_delta_time: 0,
+                },
+                SidxReference {
+                    reference_type: 1,
+                    referenced_size: 1,
+                    subsegment_duration: 1000,
+                    starts_with_sap: 1,
+                    sap_type: 1,
+                    sap_delta_time: 0,
+                },
+            ],
+            ..Default::default()
+        };
+        let top_size = top.get_size();
+        top.attach_children(vec![leaf_a, leaf_b]).unwrap();
+
+        let mut buf = Vec::new();
+        top.write_chain(&mut buf).unwrap();
+
+        let mut reader = Cursor::new(buf);
+        let read_back = SidxBox::read_chain(&mut reader).unwrap();
+
+        let index_size = top_size + leaf_a_size + leaf_b_size;
+
+        // write_chain writes the entire sidx chain ([top][leaf_a][leaf_b])
+        // contiguously with no media in between, so the first leaf's media
+        // starts only once the whole chain ends.
+        assert_eq!(
+            read_back.resolve_byte_range(500),
+            Some(SidxByteRange {
+                start: index_size,
+                end: index_size + 400,
+            })
+        );
+        // Second leaf's media follows the first leaf's.
+        assert_eq!(
+            read_back.resolve_byte_range(1500),
+            Some(SidxByteRange {
+                start: index_size + 400,
+                end: index_size + 1000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_summary_and_decoded_views() {
+        let sidx_box = SidxBox {
+            version: 0,
+            reference_id: 1,
+            timescale: 1000,
+            earliest_presentation_time: 0,
+            first_offset: 0,
+            references: vec![
+                SidxReference {
+                    reference_type: 0,
+                    referenced_size: 100,
+                    subsegment_duration: 1000,
+                    starts_with_sap: 1,
+                    sap_type: 1,
+                    sap_delta_time: 0,
+                },
+                SidxReference {
+                    reference_type: 1,
+                    referenced_size: 200,
+                    subsegment_duration: 2000,
+                    starts_with_sap: 0,
+                    sap_type: 0,
+                    sap_delta_time: 0,
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            sidx_box.summary().unwrap(),
+            "timescale=1000 earliest_presentation_time=0 reference_count=2 total_duration=3000 all_sap=false"
+        );
+
+        assert_eq!(
+            sidx_box.references[0].reference_kind(),
+            SidxReferenceKind::Media
+        );
+        assert_eq!(sidx_box.references[0].sap(), SidxSapType::Type1);
+        assert_eq!(
+            sidx_box.references[1].reference_kind(),
+            SidxReferenceKind::Index
+        );
+        assert_eq!(sidx_box.references[1].sap(), SidxSapType::Unspecified);
+
+        // The decoded meaning must reach serde output too, not just the
+        // getter methods above.
+        let json = serde_json::to_string(&sidx_box.references[0]).unwrap();
+        assert!(json.contains("\"reference_kind\":\"Media\""));
+        assert!(json.contains("\"sap\":\"Type1\""));
+        let json = serde_json::to_string(&sidx_box.references[1]).unwrap();
+        assert!(json.contains("\"reference_kind\":\"Index\""));
+        assert!(json.contains("\"sap\":\"Unspecified\""));
+    }
 }
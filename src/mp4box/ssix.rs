@@ -0,0 +1,168 @@
+use byteorder::{BigEndian, ReadBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek};
+
+use crate::mp4box::*;
+
+/// One entry of `ssix`'s per-subsegment range table: a priority `level`
+/// paired with how many bytes of that subsegment fall in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct SsixRange {
+    pub level: u8,
+    pub range_size: u32,
+}
+
+/// The range table for a single subsegment referenced by the `sidx` this
+/// `ssix` is paired with.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SsixSubsegment {
+    pub ranges: Vec<SsixRange>,
+}
+
+/// Sub-sEgment IndeX box: splits each subsegment described by a paired
+/// `sidx` into byte ranges by priority level, so an HTTP byte-range client
+/// can fetch only the levels it needs.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SsixBox {
+    pub version: u8,
+    pub flags: u32,
+    pub subsegments: Vec<SsixSubsegment>,
+}
+
+impl SsixBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SsixBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let ranges_size: u64 = self
+            .subsegments
+            .iter()
+            .map(|s| 4 + 4 * s.ranges.len() as u64)
+            .sum();
+        HEADER_SIZE + 4 + 4 + ranges_size
+    }
+}
+
+impl Mp4Box for SsixBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let range_count: usize = self.subsegments.iter().map(|s| s.ranges.len()).sum();
+        Ok(format!(
+            "subsegment_count={} range_count={}",
+            self.subsegments.len(),
+            range_count
+        ))
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SsixBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let version = reader.read_u8()?;
+        let flags = reader.read_u24::<BigEndian>()?;
+
+        let subsegment_count = reader.read_u32::<BigEndian>()?;
+        let mut subsegments = Vec::new();
+
+        for _ in 0..subsegment_count {
+            let range_count = reader.read_u32::<BigEndian>()?;
+            let mut ranges = Vec::new();
+            for _ in 0..range_count {
+                let level_and_range_size = reader.read_u32::<BigEndian>()?;
+                ranges.push(SsixRange {
+                    level: (level_and_range_size >> 24) as u8,
+                    range_size: level_and_range_size & 0x00FF_FFFF,
+                });
+            }
+            subsegments.push(SsixSubsegment { ranges });
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SsixBox {
+            version,
+            flags,
+            subsegments,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SsixBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u8(self.version)?;
+        writer.write_u24::<BigEndian>(self.flags)?;
+
+        writer.write_u32::<BigEndian>(self.subsegments.len() as u32)?;
+        for subsegment in &self.subsegments {
+            writer.write_u32::<BigEndian>(subsegment.ranges.len() as u32)?;
+            for range in &subsegment.ranges {
+                let level_and_range_size =
+                    ((range.level as u32) << 24) | (range.range_size & 0x00FF_FFFF);
+                writer.write_u32::<BigEndian>(level_and_range_size)?;
+            }
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_write_ssix_box() {
+        let ssix_box = SsixBox {
+            version: 0,
+            flags: 0,
+            subsegments: vec![
+                SsixSubsegment {
+                    ranges: vec![
+                        SsixRange {
+                            level: 1,
+                            range_size: 1000,
+                        },
+                        SsixRange {
+                            level: 2,
+                            range_size: 2000,
+                        },
+                    ],
+                },
+                SsixSubsegment {
+                    ranges: vec![SsixRange {
+                        level: 1,
+                        range_size: 500,
+                    }],
+                },
+            ],
+        };
+
+        let mut buf = Vec::new();
+        ssix_box.write_box(&mut buf).unwrap();
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SsixBox);
+        assert_eq!(header.size, ssix_box.box_size());
+
+        let read_back = SsixBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(read_back, ssix_box);
+    }
+}